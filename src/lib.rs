@@ -0,0 +1,390 @@
+use anyhow::{anyhow, Context as _};
+use serde::{Deserialize, Serialize};
+
+/// Static configuration of a [`ChatClient`].
+#[derive(Debug, Clone)]
+pub struct ChatClientConfig {
+    /// API url to send chat completion requests to.
+    pub api_url: String,
+    /// Model name passed in every request.
+    pub model: String,
+    /// Optional system message seeding the conversation.
+    pub system_message: Option<String>,
+    /// Optional proxy url (`http://…` or `socks5://…`) for all requests. When
+    /// absent the standard `HTTPS_PROXY`/`ALL_PROXY` environment variables are
+    /// honored instead.
+    pub proxy: Option<String>,
+    /// Optional connection timeout in seconds.
+    pub connect_timeout: Option<u64>,
+    /// Sampling temperature injected into the request when set.
+    pub temperature: Option<f64>,
+    /// Nucleus sampling probability injected into the request when set.
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens to generate, injected into the request when set.
+    pub max_tokens: Option<u64>,
+}
+
+/// A reusable persona: a named prompt that seeds the conversation, optionally
+/// carrying a placeholder into which the user's first line is substituted.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Role {
+    /// Name used to select the role from the CLI or the REPL.
+    pub name: String,
+    /// Prompt text. Becomes the system message, or — when
+    /// [`input_placeholder`](Self::input_placeholder) is set — a template for
+    /// the user's first message.
+    pub prompt: String,
+    /// Placeholder substituted with the user's first line, e.g. `__INPUT__`.
+    pub input_placeholder: Option<String>,
+}
+
+/// A single chat message as understood by the OpenAI chat completion API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+/// Incremental chunk of a streamed (`"stream": true`) completion.
+#[derive(Debug, Deserialize)]
+struct ChatStreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: Delta,
+}
+
+#[derive(Debug, Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+/// Stateful client holding the running conversation and talking to an
+/// OpenAI-compatible chat completion endpoint.
+pub struct ChatClient {
+    client: reqwest::blocking::Client,
+    api_key: String,
+    config: ChatClientConfig,
+    messages: Vec<Message>,
+    /// Role whose prompt templates the next user message, consumed on first use.
+    role_template: Option<Role>,
+}
+
+impl ChatClient {
+    /// Create a new client. If `config` carries a system message it seeds the
+    /// conversation as the first message. The `proxy` and `connect_timeout`
+    /// config fields configure the underlying HTTP client.
+    pub fn new(api_key: String, config: ChatClientConfig) -> anyhow::Result<Self> {
+        let mut messages = Vec::new();
+        if let Some(system_message) = &config.system_message {
+            messages.push(Message {
+                role: String::from("system"),
+                content: system_message.clone(),
+            });
+        }
+
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy).context("Failed to configure HTTP proxy")?,
+            );
+        }
+        if let Some(seconds) = config.connect_timeout {
+            builder = builder.connect_timeout(std::time::Duration::from_secs(seconds));
+        }
+        let client = builder
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            client,
+            api_key,
+            config,
+            messages,
+            role_template: None,
+        })
+    }
+
+    /// Activate `role`. A role without a placeholder replaces the system
+    /// message with its prompt; a role with a placeholder instead templates the
+    /// user's next message, substituting the placeholder with the typed line
+    /// while leaving the existing system message intact.
+    pub fn set_role(&mut self, role: Role) {
+        if role.input_placeholder.is_some() {
+            self.role_template = Some(role);
+        } else {
+            // Replace the leading system message, if any, with the role prompt.
+            if self
+                .messages
+                .first()
+                .is_some_and(|message| message.role == "system")
+            {
+                self.messages.remove(0);
+            }
+            self.messages.insert(
+                0,
+                Message {
+                    role: String::from("system"),
+                    content: role.prompt.clone(),
+                },
+            );
+            self.role_template = None;
+        }
+    }
+
+    /// The full conversation history, including the system message if any.
+    pub fn history(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Replace the conversation history with `messages`, seeding the client with
+    /// a previously saved session.
+    pub fn set_history(&mut self, messages: Vec<Message>) {
+        self.messages = messages;
+    }
+
+    /// Set a sampling parameter by name (`temperature`, `top_p`, or
+    /// `max_tokens`) from its textual value, for live tweaking between turns.
+    pub fn set_param(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "temperature" => {
+                self.config.temperature = Some(value.parse().context("Invalid temperature")?)
+            }
+            "top_p" => self.config.top_p = Some(value.parse().context("Invalid top_p")?),
+            "max_tokens" => {
+                self.config.max_tokens = Some(value.parse().context("Invalid max_tokens")?)
+            }
+            _ => return Err(anyhow!("Unknown parameter `{name}`.")),
+        }
+        Ok(())
+    }
+
+    /// Reset the in-memory history, keeping only the leading system message.
+    pub fn clear(&mut self) {
+        let keep = self
+            .messages
+            .first()
+            .is_some_and(|message| message.role == "system");
+        self.messages.truncate(if keep { 1 } else { 0 });
+    }
+
+    /// Apply a pending role template to `prompt`, consuming it so only the first
+    /// message is templated.
+    fn apply_role_template(&mut self, prompt: String) -> String {
+        match self.role_template.take() {
+            Some(Role {
+                prompt: template,
+                input_placeholder: Some(placeholder),
+                ..
+            }) => template.replace(&placeholder, &prompt),
+            _ => prompt,
+        }
+    }
+
+    /// Send `prompt` as a user message, append the assistant reply to the
+    /// conversation history, and return the reply text.
+    pub fn ask(&mut self, prompt: String) -> anyhow::Result<String> {
+        let content = self.apply_role_template(prompt);
+        self.messages.push(Message {
+            role: String::from("user"),
+            content,
+        });
+
+        let request = ChatRequest {
+            model: &self.config.model,
+            messages: &self.messages,
+            stream: None,
+            temperature: self.config.temperature,
+            top_p: self.config.top_p,
+            max_tokens: self.config.max_tokens,
+        };
+
+        let response = self
+            .client
+            .post(format!("{}chat/completions", self.config.api_url))
+            .bearer_auth(&self.api_key)
+            .json(&request)
+            .send()
+            .context("Failed to send chat completion request")?
+            .error_for_status()
+            .context("Chat completion request returned an error")?
+            .json::<ChatResponse>()
+            .context("Failed to parse chat completion response")?;
+
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Chat completion response contained no choices"))?
+            .message
+            .content;
+
+        self.messages.push(Message {
+            role: String::from("assistant"),
+            content: content.clone(),
+        });
+
+        Ok(content)
+    }
+
+    /// Like [`ask`](Self::ask), but requests a streamed response and invokes
+    /// `on_delta` for every incremental token as it arrives, so callers can
+    /// print tokens without waiting for the full reply. The deltas are
+    /// accumulated into the assistant message afterwards, keeping the
+    /// conversation history identical to the non-streaming path.
+    pub fn ask_stream(
+        &mut self,
+        prompt: String,
+        mut on_delta: impl FnMut(&str),
+    ) -> anyhow::Result<()> {
+        use std::io::Read as _;
+
+        let content = self.apply_role_template(prompt);
+        self.messages.push(Message {
+            role: String::from("user"),
+            content,
+        });
+
+        let request = ChatRequest {
+            model: &self.config.model,
+            messages: &self.messages,
+            stream: Some(true),
+            temperature: self.config.temperature,
+            top_p: self.config.top_p,
+            max_tokens: self.config.max_tokens,
+        };
+
+        let mut response = self
+            .client
+            .post(format!("{}chat/completions", self.config.api_url))
+            .bearer_auth(&self.api_key)
+            .json(&request)
+            .send()
+            .context("Failed to send chat completion request")?
+            .error_for_status()
+            .context("Chat completion request returned an error")?;
+
+        let mut content = String::new();
+        // SSE frames may be split across socket reads, so buffer raw bytes and
+        // only process whole `\n\n`-delimited events.
+        let mut buffer = Vec::new();
+        let mut read = [0u8; 4096];
+        let mut done = false;
+
+        while !done {
+            let n = response
+                .read(&mut read)
+                .context("Failed to read chat completion stream")?;
+            if n == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&read[..n]);
+
+            while let Some(pos) = find_event_boundary(&buffer) {
+                let event = buffer.drain(..pos + 2).collect::<Vec<_>>();
+                let event = String::from_utf8_lossy(&event[..pos]);
+
+                for line in event.lines() {
+                    let Some(data) = parse_sse_data(line) else {
+                        continue;
+                    };
+                    if data == "[DONE]" {
+                        done = true;
+                        break;
+                    }
+
+                    let chunk: ChatStreamChunk = serde_json::from_str(data)
+                        .context("Failed to parse chat completion stream chunk")?;
+                    if let Some(delta) = chunk
+                        .choices
+                        .into_iter()
+                        .next()
+                        .and_then(|choice| choice.delta.content)
+                    {
+                        content.push_str(&delta);
+                        on_delta(&delta);
+                    }
+                }
+            }
+        }
+
+        self.messages.push(Message {
+            role: String::from("assistant"),
+            content,
+        });
+
+        Ok(())
+    }
+}
+
+/// Return the index of the first `\n\n` event separator in `buffer`, if any.
+fn find_event_boundary(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(2).position(|window| window == b"\n\n")
+}
+
+/// Extract the payload of an SSE `data:` line, returning `None` for other
+/// fields (comments, `event:`, blank lines). The single leading space after
+/// `data:` is optional per the SSE spec, and some OpenAI-compatible backends
+/// omit it, so at most one leading space is trimmed.
+fn parse_sse_data(line: &str) -> Option<&str> {
+    let data = line.strip_prefix("data:")?;
+    Some(data.strip_prefix(' ').unwrap_or(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_event_boundary, parse_sse_data};
+
+    #[test]
+    fn event_boundary_found_at_double_newline() {
+        assert_eq!(find_event_boundary(b"data: a\n\ndata: b"), Some(7));
+        assert_eq!(find_event_boundary(b"data: a\n"), None);
+        assert_eq!(find_event_boundary(b""), None);
+    }
+
+    #[test]
+    fn sse_data_tolerates_optional_space() {
+        assert_eq!(parse_sse_data("data: {}"), Some("{}"));
+        assert_eq!(parse_sse_data("data:{}"), Some("{}"));
+        assert_eq!(parse_sse_data("data: [DONE]"), Some("[DONE]"));
+    }
+
+    #[test]
+    fn sse_data_keeps_extra_leading_spaces() {
+        // Only the single spec-defined space is stripped.
+        assert_eq!(parse_sse_data("data:  x"), Some(" x"));
+    }
+
+    #[test]
+    fn sse_data_ignores_other_fields() {
+        assert_eq!(parse_sse_data(": comment"), None);
+        assert_eq!(parse_sse_data("event: message"), None);
+        assert_eq!(parse_sse_data(""), None);
+    }
+}
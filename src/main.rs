@@ -2,12 +2,17 @@ use anyhow::{anyhow, Context as _};
 use clap::Parser;
 use colored::Colorize as _;
 use dirs::home_dir;
+use reedline::{
+    EditMode, Emacs, FileBackedHistory, Prompt, PromptEditMode, PromptHistorySearch,
+    PromptHistorySearchStatus, Reedline, Signal, ValidationResult, Validator, Vi,
+};
 use std::{
+    borrow::Cow,
     env, fs,
     io::{self, Write as _},
     path::PathBuf,
 };
-use unspoken::{ChatClient, ChatClientConfig};
+use unspoken::{ChatClient, ChatClientConfig, Message, Role};
 
 /// OpenAI chat API command line client.
 ///
@@ -27,6 +32,35 @@ struct Args {
     #[arg(short, long)]
     system: Option<String>,
 
+    /// Role to activate on launch. Roles are defined in the config file.
+    #[arg(short, long)]
+    role: Option<String>,
+
+    /// Client profile to activate on launch. Profiles are defined in the config file.
+    #[arg(long)]
+    client: Option<String>,
+
+    /// Proxy url. Example: "socks5://localhost:1080".
+    #[arg(short, long)]
+    proxy: Option<String>,
+
+    /// Connection timeout in seconds.
+    #[arg(long)]
+    connect_timeout: Option<u64>,
+
+    /// Session to load on launch and save to. Stored under
+    /// $HOME/.config/unspoken/sessions/.
+    #[arg(long)]
+    session: Option<String>,
+
+    /// Sampling temperature.
+    #[arg(short, long)]
+    temperature: Option<f64>,
+
+    /// Maximum number of tokens to generate.
+    #[arg(long)]
+    max_tokens: Option<u64>,
+
     /// Config file location. Default: $HOME/.config/unspoken.toml.
     #[arg(short, long)]
     config: Option<PathBuf>,
@@ -38,13 +72,48 @@ struct Config {
     url: Option<String>,
     model: Option<String>,
     system_message: Option<String>,
+    proxy: Option<String>,
+    connect_timeout: Option<u64>,
+    keybindings: Option<String>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    max_tokens: Option<u64>,
+    #[serde(default)]
+    roles: Vec<Role>,
+    #[serde(default)]
+    clients: Vec<ClientProfile>,
 }
 
-struct AppConfiguration {
+/// A named provider profile targeting one OpenAI-compatible endpoint.
+#[derive(Debug, serde::Deserialize)]
+struct ClientProfile {
+    name: String,
+    url: Option<String>,
+    model: Option<String>,
+    api_key: Option<String>,
+    api_key_env: Option<String>,
+}
+
+/// A client profile resolved into everything needed to build a [`ChatClient`].
+struct ResolvedClient {
+    name: String,
     api_key: String,
-    api_url: String,
-    model: String,
-    system_message: Option<String>,
+    config: ChatClientConfig,
+}
+
+impl ResolvedClient {
+    fn build(&self) -> anyhow::Result<ChatClient> {
+        ChatClient::new(self.api_key.clone(), self.config.clone())
+    }
+}
+
+struct AppConfiguration {
+    clients: Vec<ResolvedClient>,
+    active: usize,
+    roles: Vec<Role>,
+    role: Option<String>,
+    session: Option<String>,
+    keybindings: Option<String>,
 }
 
 impl AppConfiguration {
@@ -53,6 +122,13 @@ impl AppConfiguration {
             url,
             model,
             system,
+            role,
+            client,
+            proxy,
+            connect_timeout,
+            session,
+            temperature,
+            max_tokens,
             config,
         } = args;
 
@@ -92,71 +168,487 @@ impl AppConfiguration {
             }
         };
 
-        let api_key = env::var("OPENAI_API_KEY").or_else(|_| {
-            config
+        let system_message =
+            system.or_else(|| config.as_ref().and_then(|c| c.system_message.clone()));
+
+        let proxy = proxy.or_else(|| config.as_ref().and_then(|c| c.proxy.clone()));
+
+        let connect_timeout =
+            connect_timeout.or_else(|| config.as_ref().and_then(|c| c.connect_timeout));
+
+        let keybindings = config.as_ref().and_then(|c| c.keybindings.clone());
+
+        let temperature = temperature.or_else(|| config.as_ref().and_then(|c| c.temperature));
+        let top_p = config.as_ref().and_then(|c| c.top_p);
+        let max_tokens = max_tokens.or_else(|| config.as_ref().and_then(|c| c.max_tokens));
+
+        let roles = config
+            .as_ref()
+            .map(|c| c.roles.clone())
+            .unwrap_or_default();
+
+        // Resolve the list of named client profiles. When none are configured
+        // we synthesize a single "default" profile from the top-level config,
+        // preserving the original single-endpoint behavior.
+        let profiles = config.as_ref().map(|c| c.clients.as_slice()).unwrap_or(&[]);
+        let mut clients = if profiles.is_empty() {
+            let api_key = env::var("OPENAI_API_KEY").or_else(|_| {
+                config
+                    .as_ref()
+                    .and_then(|c| c.api_key.clone())
+                    .ok_or(anyhow!("Set `api_key` in config or `OPENAI_API_KEY` env."))
+            })?;
+
+            let api_url = config
                 .as_ref()
-                .map(|c| c.api_key.clone())
-                .flatten()
-                .ok_or(anyhow!("Set `api_key` in config or `OPENAI_API_KEY` env."))
-        })?;
+                .and_then(|c| c.url.clone())
+                .unwrap_or_else(default_api_url);
 
-        let api_url = url
-            .or_else(|| config.as_ref().map(|c| c.url.clone()).flatten())
-            .unwrap_or_else(|| String::from("https://models.inference.ai.azure.com/"));
+            let model = config
+                .as_ref()
+                .and_then(|c| c.model.clone())
+                .unwrap_or_else(default_model);
 
-        let model = model
-            .or_else(|| config.as_ref().map(|c| c.model.clone()).flatten())
-            .unwrap_or_else(|| String::from("gpt-4o"));
+            vec![ResolvedClient {
+                name: String::from("default"),
+                api_key,
+                config: ChatClientConfig {
+                    api_url,
+                    model,
+                    system_message: system_message.clone(),
+                    proxy: proxy.clone(),
+                    connect_timeout,
+                    temperature,
+                    top_p,
+                    max_tokens,
+                },
+            }]
+        } else {
+            profiles
+                .iter()
+                .map(|profile| {
+                    Ok(ResolvedClient {
+                        name: profile.name.clone(),
+                        api_key: resolve_api_key(&profile.api_key, &profile.api_key_env)
+                            .with_context(|| {
+                                format!("Failed to resolve API key for client `{}`", profile.name)
+                            })?,
+                        config: ChatClientConfig {
+                            api_url: profile.url.clone().unwrap_or_else(default_api_url),
+                            model: profile.model.clone().unwrap_or_else(default_model),
+                            system_message: system_message.clone(),
+                            proxy: proxy.clone(),
+                            connect_timeout,
+                            temperature,
+                            top_p,
+                            max_tokens,
+                        },
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
 
-        let system_message =
-            system.or_else(|| config.as_ref().map(|c| c.system_message.clone()).flatten());
+        // Select the active client, defaulting to the first.
+        let active = match client {
+            Some(name) => clients
+                .iter()
+                .position(|c| c.name == name)
+                .ok_or_else(|| anyhow!("Unknown client `{name}`."))?,
+            None => 0,
+        };
+
+        // CLI `--url`/`--model` overrides apply to the active client.
+        if let Some(url) = url {
+            clients[active].config.api_url = url;
+        }
+        if let Some(model) = model {
+            clients[active].config.model = model;
+        }
 
         Ok(Self {
-            api_key,
-            api_url,
-            model,
-            system_message,
+            clients,
+            active,
+            roles,
+            role,
+            session,
+            keybindings,
         })
     }
 }
 
+/// Path to the session file for `name`, creating the sessions directory if
+/// necessary.
+fn session_path(name: &str) -> anyhow::Result<PathBuf> {
+    let dir = home_dir()
+        .ok_or_else(|| anyhow!("Cannot determine home directory for sessions."))?
+        .join(".config/unspoken/sessions");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create sessions directory {}", dir.display()))?;
+    Ok(dir.join(format!("{name}.json")))
+}
+
+/// Serialize `chat`'s history to the session file for `name`.
+fn save_session(chat: &ChatClient, name: &str) -> anyhow::Result<()> {
+    let path = session_path(name)?;
+    let json =
+        serde_json::to_string_pretty(chat.history()).context("Failed to serialize session")?;
+    fs::write(&path, json)
+        .with_context(|| format!("Failed to write session file {}", path.display()))?;
+    Ok(())
+}
+
+/// Seed `chat` with the history stored in the session file for `name`.
+fn load_session(chat: &mut ChatClient, name: &str) -> anyhow::Result<()> {
+    let path = session_path(name)?;
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read session file {}", path.display()))?;
+    let messages: Vec<Message> =
+        serde_json::from_str(&json).context("Failed to parse session file")?;
+    chat.set_history(messages);
+    Ok(())
+}
+
+fn default_api_url() -> String {
+    String::from("https://models.inference.ai.azure.com/")
+}
+
+fn default_model() -> String {
+    String::from("gpt-4o")
+}
+
+/// Resolve a profile's API key from an inline value, a named environment
+/// variable, or the standard `OPENAI_API_KEY` fallback.
+fn resolve_api_key(
+    api_key: &Option<String>,
+    api_key_env: &Option<String>,
+) -> anyhow::Result<String> {
+    if let Some(api_key) = api_key {
+        return Ok(api_key.clone());
+    }
+    if let Some(name) = api_key_env {
+        return env::var(name).with_context(|| format!("Environment variable `{name}` not set."));
+    }
+    env::var("OPENAI_API_KEY")
+        .map_err(|_| anyhow!("Set `api_key`/`api_key_env` in client or `OPENAI_API_KEY` env."))
+}
+
 fn main() -> anyhow::Result<()> {
     let AppConfiguration {
-        api_key,
-        api_url,
-        model,
-        system_message,
+        clients,
+        active,
+        roles,
+        role,
+        session,
+        keybindings,
     } = AppConfiguration::init(Args::parse())?;
 
-    let mut chat = ChatClient::new(
-        api_key,
-        ChatClientConfig {
-            api_url,
-            model,
-            system_message,
-        },
-    );
+    let mut chat = clients[active].build()?;
 
-    let you = "You:".bold().red();
     let assistant = "Assistant:".bold().green();
 
-    print!("{} ", you);
-    io::stdout().flush()?;
+    // Seed the conversation from a saved session if one exists under this name.
+    let mut session = session;
+    if let Some(name) = &session {
+        if session_path(name)?.exists() {
+            load_session(&mut chat, name)?;
+        }
+    }
 
-    for line in std::io::stdin().lines() {
-        match chat.ask(line?) {
-            Ok(response) => {
-                print!("\n{} {response}\n\n{} ", assistant, you);
-            }
+    // Apply the role after loading so an explicit `--role` takes precedence over
+    // a resumed session's system message rather than being clobbered by it.
+    if let Some(name) = &role {
+        activate_role(&mut chat, &roles, name)?;
+    }
+
+    let mut line_editor = build_line_editor(keybindings.as_deref())?;
+    let prompt = ChatPrompt;
+
+    loop {
+        let line = match line_editor.read_line(&prompt) {
+            Ok(Signal::Success(line)) => line,
+            // Ctrl-C / Ctrl-D leave the REPL.
+            Ok(Signal::CtrlC) | Ok(Signal::CtrlD) => break,
             Err(e) => {
                 eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow());
-                print!("{} ", you);
+                break;
+            }
+        };
+        // Join backslash-continued lines, dropping the continuation markers.
+        let line = join_continuations(&line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Handle REPL dot-commands before talking to the model.
+        if line == ".exit" {
+            break;
+        }
+        if line == ".help" {
+            print_help();
+            continue;
+        }
+        if line == ".clear" {
+            chat.clear();
+            continue;
+        }
+        if let Some(name) = line.strip_prefix(".role ") {
+            if let Err(e) = activate_role(&mut chat, &roles, name.trim()) {
+                eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow());
+            }
+            continue;
+        }
+        if let Some(name) = line.strip_prefix(".client ") {
+            match clients.iter().find(|c| c.name == name.trim()) {
+                Some(resolved) => match resolved.build() {
+                    Ok(mut new_chat) => {
+                        // Carry the running conversation over to the new backend
+                        // so switching clients doesn't throw away context.
+                        new_chat.set_history(chat.history().to_vec());
+                        chat = new_chat;
+                    }
+                    Err(e) => eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow()),
+                },
+                None => eprintln!(
+                    "{} {}",
+                    "Error:".yellow(),
+                    format!("Unknown client `{}`.", name.trim()).yellow()
+                ),
+            }
+            continue;
+        }
+        if line == ".save" || line.starts_with(".save ") {
+            let name = line.strip_prefix(".save ").map(str::trim);
+            let result = match name.map(str::to_string).or_else(|| session.clone()) {
+                Some(name) => {
+                    session = Some(name.clone());
+                    save_session(&chat, &name)
+                }
+                None => Err(anyhow!("No session name. Use `.save <name>`.")),
+            };
+            if let Err(e) = result {
+                eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow());
+            }
+            continue;
+        }
+        if let Some(name) = line.strip_prefix(".load ") {
+            let name = name.trim();
+            match load_session(&mut chat, name) {
+                Ok(()) => session = Some(name.to_string()),
+                Err(e) => eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow()),
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix(".set ") {
+            let result = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, value] => chat.set_param(name, value),
+                _ => Err(anyhow!("Usage: .set <parameter> <value>")),
+            };
+            if let Err(e) = result {
+                eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow());
             }
+            continue;
+        }
+
+        print!("{} ", assistant);
+        io::stdout().flush()?;
+
+        let result = chat.ask_stream(line.to_string(), |delta| {
+            print!("{delta}");
+            let _ = io::stdout().flush();
+        });
+
+        match result {
+            Ok(()) => println!("\n"),
+            Err(e) => eprintln!("\n{} {}", "Error:".yellow(), e.to_string().yellow()),
         }
         io::stdout().flush()?;
     }
 
-    println!("");
+    Ok(())
+}
+
+/// Build the reedline editor with on-disk history, multiline support, and the
+/// configured keybindings (`emacs` by default, or `vi`).
+fn build_line_editor(keybindings: Option<&str>) -> anyhow::Result<Reedline> {
+    let mut editor = Reedline::create().with_validator(Box::new(ChatValidator));
+
+    if let Some(dir) = home_dir().map(|home| home.join(".config/unspoken")) {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create config directory {}", dir.display()))?;
+        let history = FileBackedHistory::with_file(1000, dir.join("history.txt"))
+            .context("Failed to open history file")?;
+        editor = editor.with_history(Box::new(history));
+    }
+
+    let edit_mode: Box<dyn EditMode> = match keybindings {
+        None | Some("emacs") => Box::new(Emacs::default()),
+        Some("vi") => Box::new(Vi::default()),
+        Some(other) => {
+            return Err(anyhow!(
+                "Unknown keybindings `{other}`. Use `emacs` or `vi`."
+            ))
+        }
+    };
+
+    Ok(editor.with_edit_mode(edit_mode))
+}
 
+/// Print the list of available dot-commands.
+fn print_help() {
+    println!(
+        "Commands:\n  \
+         .help            show this help\n  \
+         .exit            quit\n  \
+         .clear           reset the conversation history\n  \
+         .role <name>     activate a role\n  \
+         .client <name>   switch the active client\n  \
+         .save [name]     save the current session\n  \
+         .load <name>     load a saved session\n  \
+         .set <k> <v>     set a sampling parameter (temperature, top_p, max_tokens)"
+    );
+}
+
+/// Prompt rendering a red `You:` marker for reedline.
+struct ChatPrompt;
+
+impl Prompt for ChatPrompt {
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("{} ", "You:".bold().red()))
+    }
+
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        Cow::Borrowed("")
+    }
+
+    fn render_prompt_indicator(&self, _edit_mode: PromptEditMode) -> Cow<'_, str> {
+        Cow::Borrowed("")
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        Cow::Borrowed("... ")
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<'_, str> {
+        let prefix = match history_search.status {
+            PromptHistorySearchStatus::Passing => "",
+            PromptHistorySearchStatus::Failing => "failing ",
+        };
+        Cow::Owned(format!(
+            "({}reverse-search: {}) ",
+            prefix, history_search.term
+        ))
+    }
+}
+
+/// Whether `line` ends with a backslash continuation marker — i.e. an odd
+/// number of trailing backslashes. An even count is a run of escaped
+/// backslashes (literal content such as LaTeX `\\`) and does not continue.
+fn ends_with_continuation(line: &str) -> bool {
+    line.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+/// Join backslash-continued physical lines into the message to send, dropping
+/// the single trailing backslash that served as the continuation marker and
+/// keeping the newline. A doubled `\\` at a line end is literal and is left
+/// intact as two backslashes, so content that ends in `\\` (such as LaTeX line
+/// breaks) can be sent; a line end of exactly one backslash cannot be sent,
+/// since escaping it keeps both backslashes.
+fn join_continuations(input: &str) -> String {
+    let mut out = String::new();
+    let mut lines = input.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_some() && ends_with_continuation(line) {
+            out.push_str(&line[..line.len() - 1]);
+            out.push('\n');
+        } else {
+            out.push_str(line);
+            if lines.peek().is_some() {
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Validator that lets users compose multi-line messages by hand: a line
+/// ending in a backslash continues onto the next line, and the message is
+/// submitted once a line does not. The marker backslash is stripped by
+/// [`join_continuations`] before sending; a doubled `\\` is kept so content
+/// that genuinely ends in a backslash can still be expressed. Pasted blocks
+/// arrive as a single buffered edit and submit as a whole, and dot-commands
+/// submit immediately.
+struct ChatValidator;
+
+impl Validator for ChatValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        let last = line.rsplit('\n').next().unwrap_or(line);
+        if !line.starts_with('.') && ends_with_continuation(last) {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Complete
+        }
+    }
+}
+
+/// Look up `name` in `roles` and activate it on `chat`.
+fn activate_role(chat: &mut ChatClient, roles: &[Role], name: &str) -> anyhow::Result<()> {
+    let role = roles
+        .iter()
+        .find(|role| role.name == name)
+        .ok_or_else(|| anyhow!("Unknown role `{name}`."))?;
+    chat.set_role(role.clone());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continuation_marker_detected_on_odd_backslashes() {
+        assert!(ends_with_continuation("foo\\"));
+        assert!(!ends_with_continuation("foo"));
+        assert!(!ends_with_continuation("foo\\\\"));
+        assert!(ends_with_continuation("foo\\\\\\"));
+    }
+
+    #[test]
+    fn join_drops_marker_and_keeps_newline() {
+        assert_eq!(join_continuations("foo\\\nbar"), "foo\nbar");
+    }
+
+    #[test]
+    fn join_keeps_escaped_trailing_backslash() {
+        // A doubled backslash is literal content, not a continuation marker.
+        assert_eq!(join_continuations("foo\\\\\nbar"), "foo\\\\\nbar");
+    }
+
+    #[test]
+    fn join_leaves_single_line_untouched() {
+        assert_eq!(join_continuations("hello world"), "hello world");
+    }
+
+    #[test]
+    fn validator_continues_on_trailing_backslash() {
+        assert!(matches!(
+            ChatValidator.validate("foo\\"),
+            ValidationResult::Incomplete
+        ));
+        assert!(matches!(
+            ChatValidator.validate("foo"),
+            ValidationResult::Complete
+        ));
+    }
+
+    #[test]
+    fn validator_submits_dot_commands_immediately() {
+        assert!(matches!(
+            ChatValidator.validate(".help"),
+            ValidationResult::Complete
+        ));
+    }
+}